@@ -1,4 +1,10 @@
 //! CFG constants
+//!
+//! These are baked in at compile time, so they can only ever describe the target that
+//! built the binary, not the kernel it's actually running under. Prefer
+//! [`crate::os::uname::Uname`] when the running system's live `uname(2)` data is needed;
+//! these constants remain as its fallback when the `uname(2)` call fails and for the
+//! `-o`/operating-system field, which `utsname` has no portable equivalent for.
 
 // TODO: Add illumos target once it's done
 #[cfg(target_os = "linux")]
@@ -0,0 +1,36 @@
+//! Read-ahead hints for large, strictly sequential file reads.
+//!
+//! [`advise_sequential`] is a thin wrapper around `posix_fadvise(2)`, meant to be called
+//! right after opening a file that a utility is about to stream through once, start to
+//! end (e.g. `unexpand`'s line-by-line pass). It never changes what ends up being read,
+//! only how eagerly the kernel prefetches it, and is a no-op on platforms where `libc`
+//! doesn't expose `posix_fadvise`.
+
+use std::os::unix::io::RawFd;
+
+/// Hints to the kernel that `fd` will be read sequentially, start to end, so it can
+/// prefetch more aggressively than its default heuristic would.
+///
+/// This is purely a throughput hint: the syscall's return value is ignored, and on
+/// platforms without `posix_fadvise` this does nothing at all.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "dragonfly"
+))]
+pub fn advise_sequential(fd: RawFd) {
+    unsafe {
+        libc::posix_fadvise(fd, 0, 0, libc::POSIX_FADV_SEQUENTIAL);
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "dragonfly"
+)))]
+pub fn advise_sequential(_fd: RawFd) {}
@@ -1,21 +1,140 @@
 //! Module to abstract libc function that get user login name.
 
-use std::ffi::CStr;
+use std::{
+    env,
+    ffi::CStr,
+    mem::MaybeUninit,
+    os::unix::ffi::OsStrExt,
+};
+
+use bstr::BString;
+
+use libc::{c_char, getpwuid_r, getuid, passwd};
 
 // libc crate doesnt have getlogin_r, cuserid on linux target
-// use libc::{getlogin, getlogin_r, cuserid};
+#[cfg(not(target_os = "linux"))]
+use libc::getlogin_r;
+#[cfg(target_os = "linux")]
 use libc::getlogin;
 
-use bstr::{BString};
+/// Initial size of the buffer passed to `getlogin_r`, grown and retried on `ERANGE`.
+const LOGIN_NAME_BUF_INIT: usize = 256;
+
+/// Where a login name resolved by [`login_name`] came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LoginNameSource {
+    /// Resolved from the controlling terminal via `getlogin_r` (`getlogin` on Linux).
+    Terminal,
+    /// Resolved from the `LOGNAME` environment variable.
+    Logname,
+    /// Resolved from the `USER` environment variable.
+    User,
+    /// Resolved from the passwd entry of the real UID via `getpwuid_r`.
+    Passwd,
+}
+
+/// This function returns the name of the user logged in on the controlling terminal of
+/// the process if found, falling back to the environment and then to the passwd database.
+///
+/// This is kept for compatibility with callers that don't care where the name came from.
+pub fn user_login_name() -> Option<BString> { login_name().map(|(name, _)| name) }
+
+/// Resolves the login name of the calling process, returning the name alongside the
+/// source that produced it.
+///
+/// This tries, in order:
+/// 1. The login name attached to the controlling terminal, via the reentrant `getlogin_r`
+///    (on Linux, via the non-reentrant `getlogin` instead, which is not thread-safe);
+/// 2. The `LOGNAME` environment variable;
+/// 3. The `USER` environment variable;
+/// 4. `getpwuid_r(getuid(), ...)`, mapping the real UID back to a passwd entry.
+///
+/// This mirrors the fallback chain platform standard libraries use internally, so it
+/// works for daemons and detached processes that have no controlling terminal.
+pub fn login_name() -> Option<(BString, LoginNameSource)> {
+    getlogin_r_name()
+        .map(|name| (name, LoginNameSource::Terminal))
+        .or_else(|| env_var_name("LOGNAME").map(|name| (name, LoginNameSource::Logname)))
+        .or_else(|| env_var_name("USER").map(|name| (name, LoginNameSource::User)))
+        .or_else(|| passwd_name().map(|name| (name, LoginNameSource::Passwd)))
+}
+
+/// Reads an environment variable as raw bytes rather than through the UTF-8-validating
+/// `env::var`, so a non-UTF-8 login name isn't silently skipped in favor of a less
+/// accurate fallback.
+fn env_var_name(key: &str) -> Option<BString> {
+    env::var_os(key).map(|val| BString::from(val.as_bytes()))
+}
+
+/// Queries the controlling terminal's login name through the reentrant `getlogin_r`,
+/// growing the caller-owned buffer and retrying on `ERANGE`.
+#[cfg(not(target_os = "linux"))]
+fn getlogin_r_name() -> Option<BString> {
+    let mut len = LOGIN_NAME_BUF_INIT;
+
+    loop {
+        let mut buf: Vec<c_char> = vec![0; len];
+
+        let res = unsafe { getlogin_r(buf.as_mut_ptr(), buf.len()) };
+
+        if res == 0 {
+            let name = unsafe { CStr::from_ptr(buf.as_ptr()) };
+            return Some(BString::from(name.to_bytes()));
+        }
 
-/// This function return the the name of the user logged in on the controlling terminal of the process if found.
-pub fn user_login_name() -> Option<BString> {
+        if res == libc::ERANGE {
+            len *= 2;
+            continue;
+        }
+
+        return None;
+    }
+}
+
+/// Queries the controlling terminal's login name through `getlogin(3)`.
+///
+/// `getlogin_r`/`cuserid` aren't exposed by the `libc` crate on this target, so this falls
+/// back to the non-reentrant `getlogin`, which returns a pointer to a static buffer owned
+/// by libc; we copy it out before returning, but a racing call from another thread of the
+/// same process could still step on it while we read it.
+#[cfg(target_os = "linux")]
+fn getlogin_r_name() -> Option<BString> {
     let res = unsafe { getlogin() };
 
     if res.is_null() {
-        None
-    } else {
-        let name = unsafe { CStr::from_ptr(res) };
-        Some(BString::from(name.to_bytes()))
+        return None;
+    }
+
+    let name = unsafe { CStr::from_ptr(res) };
+    Some(BString::from(name.to_bytes()))
+}
+
+/// Maps the real UID of the calling process back to a passwd entry's name, growing the
+/// caller-owned buffer and retrying on `ERANGE` the same way [`getlogin_r_name`] does on
+/// non-Linux targets.
+fn passwd_name() -> Option<BString> {
+    let mut len = LOGIN_NAME_BUF_INIT;
+
+    loop {
+        let mut buf: Vec<c_char> = vec![0; len];
+        let mut pwd = MaybeUninit::<passwd>::uninit();
+        let mut result: *mut passwd = std::ptr::null_mut();
+
+        let res = unsafe {
+            getpwuid_r(getuid(), pwd.as_mut_ptr(), buf.as_mut_ptr(), buf.len(), &mut result)
+        };
+
+        if res == 0 && !result.is_null() {
+            let pwd = unsafe { pwd.assume_init() };
+            let name = unsafe { CStr::from_ptr(pwd.pw_name) };
+            return Some(BString::from(name.to_bytes()));
+        }
+
+        if res == libc::ERANGE {
+            len *= 2;
+            continue;
+        }
+
+        return None;
     }
 }
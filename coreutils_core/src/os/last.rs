@@ -0,0 +1,231 @@
+//! Session reconstruction from a utmp/wtmp database, the core of a `last`-style utility.
+
+use std::collections::HashMap;
+
+use bstr::{BStr, BString};
+
+use time::{Duration, PrimitiveDateTime as DateTime};
+
+use super::{
+    utmpx::{Utmpx, UtmpxKind, UtmpxSet},
+    TimeVal,
+};
+
+/// How a [`Session`] came to an end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SessionEnd {
+    /// Closed by a matching `DeadProcess` entry, or, for a reboot pseudo-session, by its
+    /// `ShutdownProcess` entry.
+    LoggedOut,
+    /// Cut short by a `BootTime` entry; the system went down while the session was open.
+    Crashed,
+    /// Still open at the end of the database.
+    StillLoggedIn,
+}
+
+/// A reconstructed login session, or a reboot pseudo-session emitted from a
+/// `BootTime`/`ShutdownProcess` pair.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Session {
+    /// User that logged in. For reboot pseudo-sessions, the user recorded on the
+    /// `BootTime` entry.
+    pub user:    BString,
+    /// Host the user logged in from, if any.
+    pub host:    BString,
+    /// Terminal line (tty) the session happened on.
+    pub line:    BString,
+    /// Time the session started.
+    pub login:   TimeVal,
+    /// Time the session ended, if it has.
+    pub logout:  Option<TimeVal>,
+    /// How long the session lasted, if it has ended.
+    pub duration: Option<Duration>,
+    /// How the session ended.
+    pub end:     SessionEnd,
+}
+
+impl Session {
+    /// Get the time the session started, as a full date and time.
+    pub fn login_time(&self) -> DateTime { to_datetime(self.login) }
+
+    /// Get the time the session ended, as a full date and time, if it has.
+    pub fn logout_time(&self) -> Option<DateTime> { self.logout.map(to_datetime) }
+}
+
+/// Converts a `TimeVal` into a full date and time.
+fn to_datetime(tv: TimeVal) -> DateTime {
+    DateTime::from_unix_timestamp(tv.tv_sec as i64) + Duration::microseconds(tv.tv_usec as i64)
+}
+
+/// Closes a session opened by `start`, ending it at `end_time` for the given reason.
+fn close_session(start: &Utmpx, end_time: TimeVal, end: SessionEnd) -> Session {
+    let duration = to_datetime(end_time) - to_datetime(start.timeval());
+
+    Session {
+        user: start.user().to_owned(),
+        host: start.host().to_owned(),
+        line: start.device_name().to_owned(),
+        login: start.timeval(),
+        logout: Some(end_time),
+        duration: Some(duration),
+        end,
+    }
+}
+
+/// Emits a reboot pseudo-session spanning from a `BootTime` entry to its matching
+/// `ShutdownProcess` entry.
+fn reboot_session(boot: &Utmpx, shutdown_time: TimeVal) -> Session {
+    close_session(boot, shutdown_time, SessionEnd::LoggedOut)
+}
+
+/// Leaves a session open at end-of-file; it is still logged in.
+fn open_session(start: &Utmpx) -> Session {
+    Session {
+        user: start.user().to_owned(),
+        host: start.host().to_owned(),
+        line: start.device_name().to_owned(),
+        login: start.timeval(),
+        logout: None,
+        duration: None,
+        end: SessionEnd::StillLoggedIn,
+    }
+}
+
+/// Reconstructs completed login sessions (and reboot pseudo-sessions) from a `UtmpxSet`.
+///
+/// Entries are walked in `timeval` order, keeping one open entry per `line` (tty): a
+/// `LoginProcess`/`UserProcess` entry opens a session, and a matching `DeadProcess` on the
+/// same line closes it. A `BootTime` entry closes every session still open as a crash, and
+/// is itself paired with the next `ShutdownProcess` entry into a reboot pseudo-session.
+/// Sessions that are still open once every entry has been consumed come out with
+/// `logout: None`, including a `BootTime` with no matching `ShutdownProcess` — the
+/// system's current, still-running boot.
+pub fn sessions(set: &UtmpxSet) -> impl Iterator<Item = Session> {
+    let mut entries: Vec<&Utmpx> = set.iter().collect();
+    entries.sort_by_key(|utm| (utm.timeval().tv_sec, utm.timeval().tv_usec));
+
+    let mut open: HashMap<BString, Utmpx> = HashMap::new();
+    let mut pending_boot: Option<Utmpx> = None;
+    let mut sessions = Vec::new();
+
+    for utm in entries {
+        match utm.entry_type() {
+            UtmpxKind::UserProcess | UtmpxKind::LoginProcess => {
+                open.insert(BString::from(utm.device_name()), utm.clone());
+            },
+            UtmpxKind::DeadProcess => {
+                let line: &BStr = utm.device_name();
+                if let Some(start) = open.remove(line) {
+                    sessions.push(close_session(&start, utm.timeval(), SessionEnd::LoggedOut));
+                }
+            },
+            UtmpxKind::BootTime => {
+                for (_, start) in open.drain() {
+                    sessions.push(close_session(&start, utm.timeval(), SessionEnd::Crashed));
+                }
+                pending_boot = Some(utm.clone());
+            },
+            UtmpxKind::ShutdownProcess => {
+                if let Some(boot) = pending_boot.take() {
+                    sessions.push(reboot_session(&boot, utm.timeval()));
+                }
+            },
+            _ => {},
+        }
+    }
+
+    sessions.extend(open.values().map(open_session));
+    // A `BootTime` with no later `ShutdownProcess` is the system's current, still-running
+    // boot; surface it instead of silently dropping the most recent reboot.
+    sessions.extend(pending_boot.as_ref().map(open_session));
+
+    sessions.into_iter()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(sec: i64) -> TimeVal { TimeVal { tv_sec: sec as _, tv_usec: 0 } }
+
+    fn secs(tv: TimeVal) -> i64 { tv.tv_sec as i64 }
+
+    fn entry(kind: UtmpxKind, line: &str, user: &str, sec: i64) -> Utmpx {
+        Utmpx::builder().kind(kind).line(line).user(user).timeval(at(sec)).build()
+    }
+
+    #[test]
+    fn logged_out_session_closes_on_dead_process() {
+        let set: UtmpxSet = vec![
+            entry(UtmpxKind::UserProcess, "tty1", "alice", 10),
+            entry(UtmpxKind::DeadProcess, "tty1", "", 20),
+        ]
+        .into_iter()
+        .collect();
+
+        let sessions: Vec<_> = sessions(&set).collect();
+
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].user, "alice");
+        assert_eq!(sessions[0].end, SessionEnd::LoggedOut);
+        assert_eq!(sessions[0].logout.map(secs), Some(20));
+    }
+
+    #[test]
+    fn session_still_open_at_end_of_database() {
+        let set: UtmpxSet =
+            vec![entry(UtmpxKind::UserProcess, "tty1", "alice", 10)].into_iter().collect();
+
+        let sessions: Vec<_> = sessions(&set).collect();
+
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].end, SessionEnd::StillLoggedIn);
+        assert!(sessions[0].logout.is_none());
+    }
+
+    #[test]
+    fn boot_time_crashes_every_open_session() {
+        let set: UtmpxSet = vec![
+            entry(UtmpxKind::UserProcess, "tty1", "alice", 10),
+            entry(UtmpxKind::BootTime, "~", "", 20),
+        ]
+        .into_iter()
+        .collect();
+
+        let sessions: Vec<_> = sessions(&set).collect();
+
+        let crashed = sessions.iter().find(|s| s.user == "alice").unwrap();
+        assert_eq!(crashed.end, SessionEnd::Crashed);
+        assert_eq!(crashed.logout.map(secs), Some(20));
+    }
+
+    #[test]
+    fn boot_time_paired_with_shutdown_becomes_reboot_session() {
+        let set: UtmpxSet = vec![
+            entry(UtmpxKind::BootTime, "~", "", 10),
+            entry(UtmpxKind::ShutdownProcess, "~", "", 20),
+        ]
+        .into_iter()
+        .collect();
+
+        let sessions: Vec<_> = sessions(&set).collect();
+
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].end, SessionEnd::LoggedOut);
+        assert_eq!(secs(sessions[0].login), 10);
+        assert_eq!(sessions[0].logout.map(secs), Some(20));
+    }
+
+    #[test]
+    fn pending_boot_without_shutdown_is_flushed_as_still_running() {
+        let set: UtmpxSet =
+            vec![entry(UtmpxKind::BootTime, "~", "", 10)].into_iter().collect();
+
+        let sessions: Vec<_> = sessions(&set).collect();
+
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].end, SessionEnd::StillLoggedIn);
+        assert_eq!(secs(sessions[0].login), 10);
+        assert!(sessions[0].logout.is_none());
+    }
+}
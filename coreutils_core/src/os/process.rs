@@ -0,0 +1,228 @@
+//! Live process inspection, used to enrich a `Utmpx` entry's `ut_pid` with what that
+//! process is actually doing right now.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bstr::{BStr, BString, ByteSlice};
+
+use libc::{c_int, uid_t};
+
+use time::{Duration, PrimitiveDateTime as DateTime};
+
+use super::Pid;
+
+/// Live details of a process, resolved from its pid.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ProcessInfo {
+    pid:        Pid,
+    parent_pid: Pid,
+    uid:        uid_t,
+    command:    BString,
+    executable: Option<PathBuf>,
+    tty:        Option<BString>,
+    start_time: Option<i64>, // seconds since the epoch
+}
+
+impl ProcessInfo {
+    /// Get the process id.
+    pub const fn pid(&self) -> Pid { self.pid }
+
+    /// Get the parent process id.
+    pub const fn parent_pid(&self) -> Pid { self.parent_pid }
+
+    /// Get the UID the process is running as.
+    pub const fn uid(&self) -> uid_t { self.uid }
+
+    /// Get the full command line the process was started with.
+    pub fn command(&self) -> &BStr { self.command.as_bstr() }
+
+    /// Get the path to the process' executable, if it could be resolved.
+    pub fn executable(&self) -> Option<&Path> { self.executable.as_deref() }
+
+    /// Get the controlling tty of the process, if it has one.
+    pub fn tty(&self) -> Option<&BStr> { self.tty.as_ref().map(BString::as_bstr) }
+
+    /// Get the time the process started, if the platform could report it.
+    pub fn start_time(&self) -> Option<DateTime> {
+        self.start_time.map(DateTime::from_unix_timestamp)
+    }
+}
+
+/// Resolves the live details of `pid`, returning `None` if the process no longer exists
+/// or if its start time is after `login`, which means the pid has been recycled and no
+/// longer refers to the original session leader.
+pub fn process_info(pid: Pid, login: DateTime) -> Option<ProcessInfo> {
+    let info = read_process_info(pid)?;
+
+    // A recycled pid would have started after the utmp entry claims it logged in, with
+    // enough slack to account for clock rounding in either source. Platforms that can't
+    // report a start time skip the check rather than rejecting every process.
+    if let Some(start_time) = info.start_time() {
+        if start_time > login + Duration::seconds(2) {
+            return None;
+        }
+    }
+
+    Some(info)
+}
+
+#[cfg(target_os = "linux")]
+fn read_process_info(pid: Pid) -> Option<ProcessInfo> {
+    let root = PathBuf::from(format!("/proc/{}", pid));
+
+    if !root.exists() {
+        return None;
+    }
+
+    let cmdline = fs::read(root.join("cmdline")).ok()?;
+    let command = BString::from(
+        cmdline.split(|&b| b == 0).filter(|part| !part.is_empty()).collect::<Vec<_>>().join(&b' '),
+    );
+
+    let executable = fs::read_link(root.join("exe")).ok();
+
+    let stat = fs::read_to_string(root.join("stat")).ok()?;
+    // Skip "pid (comm)", whose comm field may itself contain spaces/parens.
+    let after_comm = stat.rfind(')')? + 2;
+    let fields: Vec<&str> = stat[after_comm..].split_whitespace().collect();
+    // ppid is field 4 overall, i.e. index 1 of `fields`; starttime is field 22, index 19.
+    let parent_pid: Pid = fields.get(1)?.parse().ok()?;
+    let ticks_since_boot: u64 = fields.get(19)?.parse().ok()?;
+
+    let tty = read_process_tty(&fields);
+    let uid = read_process_uid(&root)?;
+    let start_time = Some(boot_time()? + (ticks_since_boot / ticks_per_second()) as i64);
+
+    Some(ProcessInfo { pid, parent_pid, uid, command, executable, tty, start_time })
+}
+
+/// Decodes the controlling tty from the `tty_nr` field of `/proc/<pid>/stat` (index 4 of
+/// the fields following `pid (comm)`), returning `None` when the process has none.
+#[cfg(target_os = "linux")]
+fn read_process_tty(fields: &[&str]) -> Option<BString> {
+    let tty_nr: u32 = fields.get(4)?.parse().ok()?;
+    if tty_nr == 0 {
+        return None;
+    }
+
+    // Linux's "huge" dev_t encoding, as used for tty_nr in /proc/<pid>/stat.
+    let major = (tty_nr >> 8) & 0xfff;
+    let minor = (tty_nr & 0xff) | ((tty_nr >> 12) & 0xfff00);
+
+    let sys_entry = fs::read_link(format!("/sys/dev/char/{}:{}", major, minor)).ok()?;
+    let name = sys_entry.file_name()?.to_str()?;
+
+    Some(BString::from(format!("/dev/{}", name)))
+}
+
+#[cfg(target_os = "linux")]
+fn read_process_uid(root: &Path) -> Option<uid_t> {
+    let status = fs::read_to_string(root.join("status")).ok()?;
+    let line = status.lines().find(|line| line.starts_with("Uid:"))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+#[cfg(target_os = "linux")]
+fn ticks_per_second() -> u64 {
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks > 0 { ticks as u64 } else { 100 }
+}
+
+#[cfg(target_os = "linux")]
+fn boot_time() -> Option<i64> {
+    let stat = fs::read_to_string("/proc/stat").ok()?;
+    let line = stat.lines().find(|line| line.starts_with("btime"))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+#[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd", target_os = "dragonfly"))]
+fn read_process_info(pid: Pid) -> Option<ProcessInfo> {
+    use std::mem;
+
+    const CTL_KERN: c_int = 1;
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    const KERN_PROC: c_int = 14;
+    #[cfg(any(target_os = "netbsd", target_os = "openbsd", target_os = "dragonfly"))]
+    const KERN_PROC: c_int = 46;
+    const KERN_PROC_PID: c_int = 1;
+
+    let mut mib = [CTL_KERN, KERN_PROC, KERN_PROC_PID, pid as c_int];
+    let mut info: libc::kinfo_proc = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<libc::kinfo_proc>();
+
+    let res = unsafe {
+        libc::sysctl(
+            mib.as_mut_ptr(),
+            mib.len() as u32,
+            &mut info as *mut _ as *mut libc::c_void,
+            &mut len,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+
+    if res != 0 || len == 0 {
+        return None;
+    }
+
+    kinfo_proc_to_process_info(pid, &info)
+}
+
+// `kinfo_proc`'s layout differs enough across these OSes that pulling `uid`/`parent_pid`
+// out portably needs per-OS accessors; everything else is left unavailable rather than
+// guessed at.
+
+#[cfg(any(target_os = "macos", target_os = "netbsd"))]
+fn kinfo_proc_to_process_info(pid: Pid, info: &libc::kinfo_proc) -> Option<ProcessInfo> {
+    Some(ProcessInfo {
+        pid,
+        parent_pid: info.kp_eproc.e_ppid as Pid,
+        uid: info.kp_eproc.e_ucred.cr_uid,
+        command: BString::default(),
+        executable: None,
+        tty: None,
+        start_time: None,
+    })
+}
+
+#[cfg(target_os = "freebsd")]
+fn kinfo_proc_to_process_info(pid: Pid, info: &libc::kinfo_proc) -> Option<ProcessInfo> {
+    Some(ProcessInfo {
+        pid,
+        parent_pid: info.ki_ppid as Pid,
+        uid: info.ki_uid,
+        command: BString::default(),
+        executable: None,
+        tty: None,
+        start_time: None,
+    })
+}
+
+#[cfg(target_os = "dragonfly")]
+fn kinfo_proc_to_process_info(pid: Pid, info: &libc::kinfo_proc) -> Option<ProcessInfo> {
+    Some(ProcessInfo {
+        pid,
+        parent_pid: info.kp_ppid as Pid,
+        uid: info.kp_uid,
+        command: BString::default(),
+        executable: None,
+        tty: None,
+        start_time: None,
+    })
+}
+
+#[cfg(target_os = "openbsd")]
+fn kinfo_proc_to_process_info(pid: Pid, info: &libc::kinfo_proc) -> Option<ProcessInfo> {
+    Some(ProcessInfo {
+        pid,
+        parent_pid: info.p_ppid as Pid,
+        uid: info.p_uid,
+        command: BString::default(),
+        executable: None,
+        tty: None,
+        start_time: None,
+    })
+}
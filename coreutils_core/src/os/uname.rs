@@ -0,0 +1,66 @@
+//! Runtime `uname(2)` subsystem, querying the live `utsname` struct so a `uname` utility
+//! can report the running kernel's nodename, release and version, not just a
+//! compile-time sysname/arch pair.
+
+use std::{ffi::CStr, io, mem};
+
+use bstr::{BStr, BString};
+
+use libc::{c_char, utsname};
+
+/// The running kernel's `uname(2)` information.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Uname {
+    sysname:  BString,
+    nodename: BString,
+    release:  BString,
+    version:  BString,
+    machine:  BString,
+}
+
+impl Uname {
+    /// Queries the live `utsname` struct via `uname(2)`.
+    ///
+    /// Fails if the syscall itself fails; callers should fall back to the compile-time
+    /// [`HOST_OS`](crate::consts::HOST_OS)/[`MACHINE_ARCH`](crate::consts::MACHINE_ARCH)
+    /// constants in that case. `uname` is called unconditionally, so this only builds on
+    /// targets where the `libc` crate exposes it.
+    pub fn new() -> io::Result<Self> {
+        let mut uts: utsname = unsafe { mem::zeroed() };
+
+        let res = unsafe { libc::uname(&mut uts) };
+
+        if res != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Uname {
+            sysname:  cstr_field(&uts.sysname),
+            nodename: cstr_field(&uts.nodename),
+            release:  cstr_field(&uts.release),
+            version:  cstr_field(&uts.version),
+            machine:  cstr_field(&uts.machine),
+        })
+    }
+
+    /// Get the kernel name (e.g. `Linux`).
+    pub fn sysname(&self) -> &BStr { self.sysname.as_bstr() }
+
+    /// Get the network node hostname.
+    pub fn nodename(&self) -> &BStr { self.nodename.as_bstr() }
+
+    /// Get the kernel release (e.g. `5.15.0-generic`).
+    pub fn release(&self) -> &BStr { self.release.as_bstr() }
+
+    /// Get the kernel version.
+    pub fn version(&self) -> &BStr { self.version.as_bstr() }
+
+    /// Get the machine hardware name (e.g. `x86_64`).
+    pub fn machine(&self) -> &BStr { self.machine.as_bstr() }
+}
+
+/// Reads a NUL-terminated `utsname` field into a `BString`.
+fn cstr_field(field: &[c_char]) -> BString {
+    let cstr = unsafe { CStr::from_ptr(field.as_ptr()) };
+    BString::from(cstr.to_bytes())
+}
@@ -3,20 +3,21 @@
 use std::ffi::CString;
 use std::{
     collections::{hash_set, HashSet},
-    convert::TryFrom,
+    convert::{TryFrom, TryInto},
     error::Error as StdError,
     fmt::{self, Display},
-    io,
+    io, mem,
     path::Path,
+    time::{SystemTime, UNIX_EPOCH},
 };
 #[cfg(not(any(target_os = "linux", target_os = "macos")))]
 use std::{
-    fs::{self, File},
+    fs::File,
     io::{BufReader, Read},
-    mem, slice,
+    slice,
 };
 
-use super::{Pid, TimeVal};
+use super::{process::ProcessInfo, Pid, TimeVal};
 
 #[cfg(any(target_os = "linux", target_os = "netbsd"))]
 use libc::__exit_status as ExitStatus;
@@ -28,7 +29,7 @@ use libc::c_long;
 use libc::utmpxname;
 #[cfg(target_os = "solaris")]
 use libc::{c_int, c_short, exit_status as ExitStatus};
-use libc::{endutxent, getutxent, setutxent, suseconds_t, time_t, utmpx};
+use libc::{c_char, endutxent, getutxent, pututxline, setutxent, suseconds_t, time_t, utmpx};
 
 use bstr::{BStr, BString, ByteSlice};
 
@@ -132,8 +133,9 @@ pub struct Utmpx {
 }
 
 impl Utmpx {
-    /// Creates a new `Utmpx` entry from the `C` version of the structure
-    pub fn from_c_utmpx(utm: utmpx) -> Self { Self::from(utm) }
+    /// Creates a new `Utmpx` entry from the `C` version of the structure, failing if the
+    /// OS reports an `ut_type` this crate doesn't recognize.
+    pub fn from_c_utmpx(utm: utmpx) -> Result<Self, Error> { Self::try_from(utm) }
 
     /// Get user name
     pub fn user(&self) -> &BStr { self.user.as_bstr() }
@@ -186,15 +188,199 @@ impl Utmpx {
     /// Get exit status of the entry
     #[cfg(any(target_os = "linux", target_os = "netbsd", target_os = "solaris"))]
     pub const fn exit_status(&self) -> ExitStatus { self.exit }
+
+    /// Get the live details of the process that created this entry, if it is still
+    /// running and it didn't start more than two seconds after `login_time()` (otherwise
+    /// `ut_pid` has been recycled and no longer refers to the original session leader).
+    pub fn process(&self) -> Option<ProcessInfo> {
+        super::process::process_info(self.pid, self.login_time())
+    }
+
+    /// Creates a builder for a new entry, to be written to the utmp database with
+    /// [`write_record`] or [`UtmpxSet::put`].
+    pub fn builder() -> UtmpxBuilder { UtmpxBuilder::default() }
+
+    /// Assembles an entry from its core fields, defaulting the cfg-specific bookkeeping
+    /// fields that writers recording logins/logouts don't usually need to set.
+    fn new(
+        user: BString,
+        host: BString,
+        id: BString,
+        line: BString,
+        pid: Pid,
+        ut_type: UtmpxKind,
+        timeval: TimeVal,
+    ) -> Self {
+        Utmpx {
+            user,
+            host,
+            pid,
+            id,
+            line,
+            ut_type,
+            timeval,
+            #[cfg(any(target_os = "linux", target_os = "netbsd", target_os = "solaris"))]
+            exit: unsafe { mem::zeroed() },
+            #[cfg(all(target_os = "linux", any(target_arch = "x86_64")))]
+            session: 0,
+            #[cfg(target_os = "solaris")]
+            session: 0,
+            #[cfg(all(target_os = "linux", not(any(target_arch = "x86_64"))))]
+            session: 0,
+            #[cfg(any(target_os = "netbsd", target_os = "dragonfly"))]
+            session: 0,
+            #[cfg(target_os = "linux")]
+            addr_v6: [0; 4],
+            #[cfg(target_os = "netbsd")]
+            ss: unsafe { mem::zeroed() },
+            #[cfg(target_os = "solaris")]
+            syslen: 0,
+        }
+    }
 }
 
-impl From<utmpx> for Utmpx {
-    /// Converts `utmpx` to `Utmpx`.
-    ///
-    /// # Panic
-    /// This function may panic when converting a number to UtmpxKind. Since we get the number
-    /// from the OS it should never panic, but if the OS drastically change, it may panic.
-    fn from(c_utmpx: utmpx) -> Self {
+/// Builds a [`Utmpx`] entry to write to the utmp database. Defaults to a `UserProcess`
+/// entry timestamped with the current time.
+#[derive(Debug, Clone, Default)]
+pub struct UtmpxBuilder {
+    user:    BString,
+    host:    BString,
+    id:      BString,
+    line:    BString,
+    pid:     Pid,
+    ut_type: Option<UtmpxKind>,
+    timeval: Option<TimeVal>,
+}
+
+impl UtmpxBuilder {
+    /// Set the user login name.
+    pub fn user(mut self, user: impl Into<BString>) -> Self {
+        self.user = user.into();
+        self
+    }
+
+    /// Set the host name.
+    pub fn host(mut self, host: impl Into<BString>) -> Self {
+        self.host = host.into();
+        self
+    }
+
+    /// Set the record identifier (/etc/inittab id).
+    pub fn id(mut self, id: impl Into<BString>) -> Self {
+        self.id = id.into();
+        self
+    }
+
+    /// Set the device name (console/tty, lnxx).
+    pub fn line(mut self, line: impl Into<BString>) -> Self {
+        self.line = line.into();
+        self
+    }
+
+    /// Set the process id creating the entry.
+    pub fn pid(mut self, pid: Pid) -> Self {
+        self.pid = pid;
+        self
+    }
+
+    /// Set the type of the entry.
+    pub fn kind(mut self, kind: UtmpxKind) -> Self {
+        self.ut_type = Some(kind);
+        self
+    }
+
+    /// Set the time the entry was created. Defaults to the current time if unset.
+    pub fn timeval(mut self, timeval: TimeVal) -> Self {
+        self.timeval = Some(timeval);
+        self
+    }
+
+    /// Finishes the entry.
+    pub fn build(self) -> Utmpx {
+        Utmpx::new(
+            self.user,
+            self.host,
+            self.id,
+            self.line,
+            self.pid,
+            self.ut_type.unwrap_or(UtmpxKind::UserProcess),
+            self.timeval.unwrap_or_else(now),
+        )
+    }
+}
+
+/// Gets the current time as a `TimeVal`.
+fn now() -> TimeVal {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+
+    TimeVal {
+        tv_sec:  since_epoch.as_secs() as time_t,
+        tv_usec: since_epoch.subsec_micros() as suseconds_t,
+    }
+}
+
+/// Copies `src` into the fixed-width `dst` C array, null-padding or truncating as needed.
+fn copy_bytes(dst: &mut [c_char], src: &BStr) {
+    for (d, s) in dst.iter_mut().zip(src.iter().chain(std::iter::repeat(&0u8))) {
+        *d = *s as c_char;
+    }
+}
+
+/// Writes `entry` to the in-kernel utmp database, inserting or updating it.
+///
+/// Shares the same process-global `setutxent`/`endutxent` cursor as [`UtmpxReader`]; don't
+/// call this while a `UtmpxReader` over the system database is alive, or its cursor will be
+/// reset/closed out from under it.
+pub fn write_record(entry: Utmpx) -> io::Result<()> {
+    let c_utmpx = utmpx::from(entry);
+
+    let result = unsafe {
+        setutxent();
+
+        if pututxline(&c_utmpx).is_null() {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    };
+
+    unsafe { endutxent() };
+
+    result
+}
+
+/// Closes the session on `line`, rewriting its entry as a `DeadProcess` with a fresh
+/// timeval and zeroed user/host, the way `login(1)` and session managers do on logout.
+///
+/// Calls [`write_record`] internally, so the same caveat about its shared cursor with
+/// [`UtmpxReader`] applies here.
+pub fn close_session(line: impl AsRef<BStr>) -> io::Result<()> {
+    let line = line.as_ref();
+
+    let leader = UtmpxSet::system()
+        .into_iter()
+        .find(|utm| utm.device_name() == line)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no open session on this line"))?;
+
+    let closing = Utmpx::new(
+        BString::default(),
+        BString::default(),
+        leader.id,
+        leader.line,
+        leader.pid,
+        UtmpxKind::DeadProcess,
+        now(),
+    );
+
+    write_record(closing)
+}
+
+impl TryFrom<utmpx> for Utmpx {
+    type Error = Error;
+
+    /// Converts `utmpx` to `Utmpx`, failing if the OS reports an `ut_type` this crate
+    /// doesn't recognize.
+    fn try_from(c_utmpx: utmpx) -> Result<Self, Error> {
         #[cfg(not(any(target_os = "netbsd", target_os = "dragonfly")))]
         let user = {
             let cstr: String = c_utmpx
@@ -245,10 +431,7 @@ impl From<utmpx> for Utmpx {
             BString::from(cstr.as_bytes())
         };
 
-        let ut_type = match UtmpxKind::try_from(c_utmpx.ut_type) {
-            Ok(ut) => ut,
-            Err(err) => panic!(format!("{}", err)),
-        };
+        let ut_type = UtmpxKind::try_from(c_utmpx.ut_type)?;
 
         let timeval = TimeVal {
             tv_sec:  c_utmpx.ut_tv.tv_sec as time_t,
@@ -275,7 +458,7 @@ impl From<utmpx> for Utmpx {
         #[cfg(target_os = "solaris")]
         let syslen = c_utmpx.ut_syslen;
 
-        Utmpx {
+        Ok(Utmpx {
             user,
             host,
             pid,
@@ -298,16 +481,94 @@ impl From<utmpx> for Utmpx {
             ss,
             #[cfg(target_os = "solaris")]
             syslen,
+        })
+    }
+}
+
+impl From<Utmpx> for utmpx {
+    /// Converts `Utmpx` into the `C` version of the structure, null-padding and
+    /// truncating the fixed-width char arrays to fit, ready for `pututxline`.
+    fn from(utm: Utmpx) -> Self {
+        let mut c_utmpx: utmpx = unsafe { mem::zeroed() };
+
+        #[cfg(not(any(target_os = "netbsd", target_os = "dragonfly")))]
+        copy_bytes(&mut c_utmpx.ut_user, utm.user.as_bstr());
+        #[cfg(any(target_os = "netbsd", target_os = "dragonfly"))]
+        copy_bytes(&mut c_utmpx.ut_name, utm.user.as_bstr());
+
+        copy_bytes(&mut c_utmpx.ut_host, utm.host.as_bstr());
+        copy_bytes(&mut c_utmpx.ut_id, utm.id.as_bstr());
+        copy_bytes(&mut c_utmpx.ut_line, utm.line.as_bstr());
+
+        c_utmpx.ut_pid = utm.pid;
+        if let Ok(ut_type) = utm.ut_type.try_into() {
+            c_utmpx.ut_type = ut_type;
+        }
+        c_utmpx.ut_tv.tv_sec = utm.timeval.tv_sec as _;
+        c_utmpx.ut_tv.tv_usec = utm.timeval.tv_usec as _;
+
+        #[cfg(any(target_os = "linux", target_os = "netbsd", target_os = "solaris"))]
+        {
+            c_utmpx.ut_exit = utm.exit;
+        }
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "netbsd",
+            target_os = "dragonfly",
+            target_os = "solaris"
+        ))]
+        {
+            c_utmpx.ut_session = utm.session;
+        }
+        #[cfg(target_os = "linux")]
+        {
+            c_utmpx.ut_addr_v6 = utm.addr_v6;
+        }
+        #[cfg(target_os = "netbsd")]
+        {
+            c_utmpx.ut_ss = utm.ss;
+        }
+        #[cfg(target_os = "solaris")]
+        {
+            c_utmpx.ut_syslen = utm.syslen;
         }
+
+        c_utmpx
     }
 }
 
-/// A collection of Utmpx entries
+/// An owning, streaming iterator over a utmp database, following the RAII pattern of
+/// low-level syscall wrapper crates: the underlying database session is opened on
+/// construction and closed (`endutxent()`) when the reader is dropped.
+///
+/// This "owns" the session only in the RAII-scoping sense, not in the isolation sense:
+/// `setutxent()`/`getutxent()`/`endutxent()` operate on a single process-global cursor, not
+/// one private to this reader. If [`write_record`], [`close_session`], or [`UtmpxSet::put`]
+/// runs while a `UtmpxReader(UtmpxReaderKind::Syscall)` is alive, that writer's own
+/// `setutxent()`/`endutxent()` will reset or close the reader's cursor out from under it.
+/// Don't interleave reads from a live `UtmpxReader` with writes on the same thread/process.
 #[derive(Debug)]
-pub struct UtmpxSet(HashSet<Utmpx>);
+pub struct UtmpxReader(UtmpxReaderKind);
 
-impl UtmpxSet {
-    /// Creates a new collection over a utmpx entry binary file
+#[derive(Debug)]
+enum UtmpxReaderKind {
+    /// Backed by `getutxent()`, either over the running system's database or a file
+    /// selected with `utmpxname()`.
+    Syscall,
+    /// Backed by reading fixed-size `utmpx` records directly out of a file, for
+    /// platforms whose libc doesn't expose `utmpxname()`.
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    File(BufReader<File>),
+}
+
+impl UtmpxReader {
+    /// Opens an owning reader over the running system's utmp database.
+    pub fn system() -> Self {
+        unsafe { setutxent() };
+        UtmpxReader(UtmpxReaderKind::Syscall)
+    }
+
+    /// Opens an owning reader over the utmpx entry binary file at `path`.
     #[cfg(any(target_os = "linux", target_os = "macos"))]
     pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
         let file = {
@@ -318,8 +579,6 @@ impl UtmpxSet {
             CString::new(str).unwrap_or_default()
         };
 
-        let mut set = HashSet::new();
-
         unsafe {
             let res = utmpxname(file.as_ptr());
 
@@ -327,68 +586,78 @@ impl UtmpxSet {
                 return Err(io::Error::last_os_error());
             }
 
-            loop {
-                let ut = getutxent();
-                if ut.is_null() {
-                    break;
-                } else {
-                    let utm = Utmpx::from_c_utmpx(*ut);
-                    set.insert(utm);
-                }
-            }
-
-            endutxent();
+            setutxent();
         }
 
-        Ok(UtmpxSet(set))
+        Ok(UtmpxReader(UtmpxReaderKind::Syscall))
     }
 
-    /// Creates a new collection over a utmpx entry binary file
+    /// Opens an owning reader over the utmpx entry binary file at `path`.
     #[cfg(not(any(target_os = "linux", target_os = "macos")))]
     pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
-        let struct_size = mem::size_of::<utmpx>();
-        let num_bytes = fs::metadata(&path)?.len() as usize;
-        let num_structs = num_bytes / struct_size;
-        let mut reader = BufReader::new(File::open(&path)?);
-        let mut vec = Vec::with_capacity(num_structs);
-        let mut set = HashSet::with_capacity(num_structs);
-
-        unsafe {
-            let mut buffer = slice::from_raw_parts_mut(vec.as_mut_ptr() as *mut u8, num_bytes);
-            reader.read_exact(&mut buffer)?;
-            vec.set_len(num_structs);
-        }
-
-        for raw_utm in vec {
-            set.insert(Utmpx::from_c_utmpx(raw_utm));
-        }
-
-        Ok(UtmpxSet(set))
+        Ok(UtmpxReader(UtmpxReaderKind::File(BufReader::new(File::open(path)?))))
     }
+}
 
-    /// Creates a new collection geting all entries from the running system
-    pub fn system() -> Self {
-        let mut set = HashSet::new();
+impl Iterator for UtmpxReader {
+    type Item = Result<Utmpx, Error>;
 
-        unsafe {
-            setutxent();
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            UtmpxReaderKind::Syscall => {
+                let ut = unsafe { getutxent() };
 
-            loop {
-                let ut = getutxent();
                 if ut.is_null() {
-                    break;
+                    None
                 } else {
-                    let utm = Utmpx::from_c_utmpx(*ut);
-                    set.insert(utm);
+                    Some(Utmpx::from_c_utmpx(unsafe { *ut }))
                 }
-            }
+            },
+            #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+            UtmpxReaderKind::File(reader) => {
+                let mut raw_utm: utmpx = unsafe { mem::zeroed() };
+
+                let buffer = unsafe {
+                    slice::from_raw_parts_mut(
+                        &mut raw_utm as *mut utmpx as *mut u8,
+                        mem::size_of::<utmpx>(),
+                    )
+                };
+
+                match reader.read_exact(buffer) {
+                    Ok(()) => Some(Utmpx::from_c_utmpx(raw_utm)),
+                    Err(_) => None,
+                }
+            },
+        }
+    }
+}
 
-            endutxent();
+impl Drop for UtmpxReader {
+    fn drop(&mut self) {
+        if let UtmpxReaderKind::Syscall = self.0 {
+            unsafe { endutxent() };
         }
+    }
+}
+
+/// A collection of Utmpx entries
+#[derive(Debug)]
+pub struct UtmpxSet(HashSet<Utmpx>);
 
-        UtmpxSet(set)
+impl UtmpxSet {
+    /// Creates a new collection over a utmpx entry binary file, silently skipping entries
+    /// whose `ut_type` this OS doesn't recognize. Use [`UtmpxReader::from_file`] directly
+    /// to see those failures.
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(UtmpxSet(UtmpxReader::from_file(path)?.filter_map(Result::ok).collect()))
     }
 
+    /// Creates a new collection gathering every entry from the running system, silently
+    /// skipping entries whose `ut_type` this OS doesn't recognize. Use
+    /// [`UtmpxReader::system`] directly to see those failures.
+    pub fn system() -> Self { UtmpxSet(UtmpxReader::system().filter_map(Result::ok).collect()) }
+
     /// Returns `true` if collection nas no elements
     pub fn is_empty(&self) -> bool { self.0.is_empty() }
 
@@ -397,6 +666,15 @@ impl UtmpxSet {
 
     /// Size of the collection
     pub fn len(&self) -> usize { self.0.len() }
+
+    /// Writes `entry` to the in-kernel utmp database and records it in this collection.
+    /// See [`write_record`]'s caveat about the global cursor it shares with [`UtmpxReader`].
+    pub fn put(&mut self, entry: Utmpx) -> io::Result<()> {
+        write_record(entry.clone())?;
+        self.0.insert(entry);
+
+        Ok(())
+    }
 }
 
 impl IntoIterator for UtmpxSet {
@@ -407,6 +685,12 @@ impl IntoIterator for UtmpxSet {
     fn into_iter(self) -> Self::IntoIter { self.0.into_iter() }
 }
 
+impl std::iter::FromIterator<Utmpx> for UtmpxSet {
+    fn from_iter<I: IntoIterator<Item = Utmpx>>(iter: I) -> Self {
+        UtmpxSet(iter.into_iter().collect())
+    }
+}
+
 
 // Extra trait
 macro_rules! utmpxkind_impl_from {
@@ -0,0 +1,213 @@
+//! `tee`: copy standard input to standard output and to each given file.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, stdin, stdout, BufReader, Read, Write},
+    os::unix::io::{AsRawFd, RawFd},
+    process, ptr,
+};
+
+use clap::{load_yaml, App};
+
+#[cfg(test)]
+mod tests;
+
+const BUFFER_SIZE: usize = 64 * 1024;
+
+fn main() {
+    let yaml = load_yaml!("tee.yml");
+    let matches = App::from_yaml(yaml).get_matches();
+
+    let append = matches.is_present("append");
+
+    let mut files = Vec::new();
+    for path in matches.values_of("FILE").into_iter().flatten() {
+        match OpenOptions::new().create(true).write(true).append(append).truncate(!append).open(path)
+        {
+            Ok(file) => files.push(file),
+            Err(err) => {
+                eprintln!("tee: {}: {}", path, err);
+                process::exit(1);
+            },
+        }
+    }
+
+    let stdin = stdin();
+    let stdout = stdout();
+    let mut stdout_lock = stdout.lock();
+
+    if let Err(err) = tee(stdin.lock(), &mut stdout_lock, &mut files) {
+        eprintln!("tee: {}", err);
+        process::exit(1);
+    }
+}
+
+/// Copies `input` to `stdout` and to every file in `files`.
+///
+/// When `input` is a seekable regular file, each destination is copied independently
+/// (rewinding `input` in between), which lets every one of them take the in-kernel fast
+/// path in [`copy_fd`]. Otherwise (the common case of a pipe on stdin, which can only be
+/// read once) falls back to a single buffered pass that fans each chunk out to every
+/// destination at once.
+fn tee<R: Read + AsRawFd>(
+    mut input: R,
+    stdout: &mut (impl Write + AsRawFd),
+    files: &mut [File],
+) -> io::Result<()> {
+    let input_fd = input.as_raw_fd();
+
+    if is_seekable(input_fd) {
+        copy_one(input_fd, stdout.as_raw_fd(), stdout, &mut input)?;
+
+        for file in files.iter_mut() {
+            rewind(input_fd)?;
+            let file_fd = file.as_raw_fd();
+            copy_one(input_fd, file_fd, file, &mut input)?;
+        }
+
+        return Ok(());
+    }
+
+    let reader = BufReader::with_capacity(BUFFER_SIZE, input);
+
+    let mut outputs: Vec<&mut dyn Write> = Vec::with_capacity(files.len() + 1);
+    outputs.push(stdout);
+    for file in files.iter_mut() {
+        outputs.push(file);
+    }
+
+    copy_buffer(reader, &mut MultiWriter(outputs))
+}
+
+/// Copies all of `input` (at `input_fd`) into `output` (at `output_fd`), preferring the
+/// in-kernel fast path and falling back to [`copy_buffer`] when it isn't available.
+fn copy_one<R: Read>(
+    input_fd: RawFd,
+    output_fd: RawFd,
+    output: &mut impl Write,
+    input: &mut R,
+) -> io::Result<()> {
+    if copy_fd(input_fd, output_fd)? {
+        return Ok(());
+    }
+
+    copy_buffer(BufReader::with_capacity(BUFFER_SIZE, input), output)
+}
+
+/// Copies every byte `reader` yields into `writer`, through a userspace buffer.
+pub fn copy_buffer<R: Read, W: Write>(mut reader: BufReader<R>, writer: &mut W) -> io::Result<()> {
+    let mut buffer = [0u8; BUFFER_SIZE];
+
+    loop {
+        let read = reader.read(&mut buffer)?;
+
+        if read == 0 {
+            return Ok(());
+        }
+
+        writer.write_all(&buffer[..read])?;
+    }
+}
+
+/// Copies all of `input` into `output` entirely in the kernel, without bouncing the data
+/// through a userspace buffer. Returns `Ok(true)` if it completed the whole copy this
+/// way, or `Ok(false)` if neither `copy_file_range(2)` nor `sendfile(2)` apply here (e.g.
+/// one end is a non-seekable stdin/stdout, or the platform doesn't have them), in which
+/// case the caller should fall back to [`copy_buffer`].
+fn copy_fd(input: RawFd, output: RawFd) -> io::Result<bool> {
+    match copy_file_range_loop(input, output) {
+        Ok(()) => return Ok(true),
+        Err(ref err) if is_unsupported(err) => {},
+        Err(err) => return Err(err),
+    }
+
+    match sendfile_loop(input, output) {
+        Ok(()) => Ok(true),
+        Err(ref err) if is_unsupported(err) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Whether `err` means the in-kernel copy doesn't apply to this pair of fds, rather than
+/// a real I/O failure. `copy_file_range(2)` reports `EBADF` for an output opened with
+/// `O_APPEND` (e.g. `tee -a`), so that counts as unsupported too.
+fn is_unsupported(err: &io::Error) -> bool {
+    matches!(
+        err.raw_os_error(),
+        Some(libc::ENOSYS) | Some(libc::EXDEV) | Some(libc::EINVAL) | Some(libc::EBADF)
+    )
+}
+
+#[cfg(target_os = "linux")]
+fn copy_file_range_loop(input: RawFd, output: RawFd) -> io::Result<()> {
+    loop {
+        let copied = unsafe {
+            libc::copy_file_range(input, ptr::null_mut(), output, ptr::null_mut(), BUFFER_SIZE, 0)
+        };
+
+        match copied {
+            n if n < 0 => return Err(io::Error::last_os_error()),
+            0 => return Ok(()),
+            _ => {},
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn copy_file_range_loop(_input: RawFd, _output: RawFd) -> io::Result<()> {
+    Err(io::Error::from_raw_os_error(libc::ENOSYS))
+}
+
+#[cfg(target_os = "linux")]
+fn sendfile_loop(input: RawFd, output: RawFd) -> io::Result<()> {
+    loop {
+        let copied = unsafe { libc::sendfile(output, input, ptr::null_mut(), BUFFER_SIZE) };
+
+        match copied {
+            n if n < 0 => return Err(io::Error::last_os_error()),
+            0 => return Ok(()),
+            _ => {},
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn sendfile_loop(_input: RawFd, _output: RawFd) -> io::Result<()> {
+    Err(io::Error::from_raw_os_error(libc::ENOSYS))
+}
+
+/// Whether `fd` supports seeking, i.e. is a regular file rather than a pipe or socket.
+fn is_seekable(fd: RawFd) -> bool {
+    unsafe { libc::lseek(fd, 0, libc::SEEK_CUR) >= 0 }
+}
+
+/// Seeks `fd` back to its start.
+fn rewind(fd: RawFd) -> io::Result<()> {
+    if unsafe { libc::lseek(fd, 0, libc::SEEK_SET) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Fans out every write to all wrapped destinations, the way `tee` duplicates a single
+/// input stream.
+struct MultiWriter<'a>(Vec<&'a mut dyn Write>);
+
+impl Write for MultiWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for writer in self.0.iter_mut() {
+            writer.write_all(buf)?;
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        for writer in self.0.iter_mut() {
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+}
@@ -40,6 +40,43 @@ fn tee_copy_stdin_to_file() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[test]
+fn is_seekable_regular_file_vs_pipe() -> Result<(), Box<dyn Error>> {
+    let file = NamedTempFile::new()?;
+    assert!(is_seekable(file.as_file().as_raw_fd()));
+
+    let mut fds = [0 as RawFd; 2];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+    let [read_end, write_end] = fds;
+
+    assert!(!is_seekable(read_end));
+
+    unsafe {
+        libc::close(read_end);
+        libc::close(write_end);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn copy_fd_in_kernel_fast_path() -> Result<(), Box<dyn Error>> {
+    let mut input = NamedTempFile::new()?;
+    let output = NamedTempFile::new()?;
+
+    input.write_all(b"Hello World!")?;
+    rewind(input.as_file().as_raw_fd())?;
+
+    let copied = copy_fd(input.as_file().as_raw_fd(), output.as_file().as_raw_fd())?;
+    assert!(copied);
+
+    let mut file_buffer = String::new();
+    File::open(output.path())?.read_to_string(&mut file_buffer)?;
+    assert_eq!("Hello World!".to_owned(), file_buffer);
+
+    Ok(())
+}
+
 #[test]
 fn tee_append_stdin_to_file() -> Result<(), Box<dyn Error>> {
     let buffer = "Hello World!";
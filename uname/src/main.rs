@@ -0,0 +1,53 @@
+use clap::{load_yaml, App};
+
+use coreutils_core::{
+    consts::{HOST_OS, MACHINE_ARCH},
+    os::uname::Uname,
+};
+
+fn main() {
+    let yaml = load_yaml!("uname.yml");
+    let matches = App::from_yaml(yaml).get_matches();
+
+    // The live `utsname` can report nodename/release/version, which the compile-time
+    // constants never could; fall back to the constants if the `uname(2)` call fails.
+    let uname = Uname::new().ok();
+
+    let sysname = uname.as_ref().map_or_else(|| HOST_OS.to_string(), |u| u.sysname().to_string());
+    let nodename = uname.as_ref().map_or_else(String::new, |u| u.nodename().to_string());
+    let release = uname.as_ref().map_or_else(String::new, |u| u.release().to_string());
+    let version = uname.as_ref().map_or_else(String::new, |u| u.version().to_string());
+    let machine = uname.as_ref().map_or_else(|| MACHINE_ARCH.to_string(), |u| u.machine().to_string());
+    // `utsname` has no portable equivalent of the "-o" field, so this is always the
+    // compile-time constant.
+    let operating_system = HOST_OS.to_string();
+
+    let all = matches.is_present("all");
+
+    // GNU orders the fields s n r v m o regardless of the order the flags were given.
+    let mut fields = Vec::new();
+    if all || matches.is_present("sysname") {
+        fields.push(sysname.clone());
+    }
+    if all || matches.is_present("nodename") {
+        fields.push(nodename);
+    }
+    if all || matches.is_present("release") {
+        fields.push(release);
+    }
+    if all || matches.is_present("version") {
+        fields.push(version);
+    }
+    if all || matches.is_present("machine") {
+        fields.push(machine);
+    }
+    if all || matches.is_present("operating_system") {
+        fields.push(operating_system);
+    }
+
+    if fields.is_empty() {
+        fields.push(sysname);
+    }
+
+    println!("{}", fields.join(" "));
+}
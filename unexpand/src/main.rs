@@ -2,74 +2,159 @@ use std::{
     env::current_dir,
     fs::File,
     io::{prelude::BufRead, stdin, stdout, Stdout, BufReader, Write},
+    os::unix::io::AsRawFd,
     process,
 };
 
 use clap::{load_yaml, App, ArgMatches};
 
-struct Unexpand {
+use coreutils_core::fadvise::advise_sequential;
+
+#[cfg(test)]
+mod tests;
+
+/// Where tabs fall in the output, derived from the parsed `--tabs` argument.
+enum TabStops {
+    /// A single tab size `N`: stops fall at every multiple of `N`.
+    Size(usize),
+    /// An explicit, ascending list of absolute columns.
+    List(Vec<usize>),
+}
+
+impl TabStops {
+    /// Parses the `--tabs` value: a single number sets a uniform tab size, a
+    /// comma-separated list of numbers sets explicit stop columns.
+    fn parse(tabs: &[String]) -> Self {
+        if tabs.len() <= 1 {
+            let size: usize = tabs.get(0).and_then(|s| s.parse().ok()).unwrap_or(8);
+            return TabStops::Size(if size == 0 { 8 } else { size });
+        }
+
+        let mut stops: Vec<usize> = tabs.iter().filter_map(|s| s.parse().ok()).collect();
+        stops.sort_unstable();
+
+        TabStops::List(stops)
+    }
+
+    /// The next tab stop strictly after `column`, or `None` if there isn't one (only
+    /// possible with an explicit `List`, past its last entry).
+    fn next_stop(&self, column: usize) -> Option<usize> {
+        match self {
+            TabStops::Size(size) => Some((column / size + 1) * size),
+            TabStops::List(stops) => stops.iter().copied().find(|&stop| stop > column),
+        }
+    }
+}
+
+struct Unexpand<W: Write> {
     pub all: bool,
     pub first_only: bool,
     pub tabs: Vec<String>,
-    output: Stdout,
+    stops: TabStops,
+    output: W,
 }
 
-impl Unexpand {
+impl Unexpand<Stdout> {
     pub fn from_matches(matches: &ArgMatches) -> Self {
         let all = matches.is_present("all");
         let first_only = matches.is_present("first_only");
 
-        Unexpand {
-            all,
-            first_only,
-            tabs: matches
-                .value_of("tabs")
-                .unwrap_or("8")
-                .split(",")
-                .map(|s| s.to_string())
-                .collect(),
-            output: stdout()
-        }
+        let tabs: Vec<String> = matches
+            .value_of("tabs")
+            .unwrap_or("8")
+            .split(",")
+            .map(|s| s.to_string())
+            .collect();
+
+        let stops = TabStops::parse(&tabs);
+
+        Unexpand { all, first_only, tabs, stops, output: stdout() }
+    }
+}
+
+impl<W: Write> Unexpand<W> {
+    #[cfg(test)]
+    fn with_output(all: bool, first_only: bool, tabs: &[String], output: W) -> Self {
+        let stops = TabStops::parse(tabs);
+        Unexpand { all, first_only, tabs: tabs.to_vec(), stops, output }
     }
 
     pub fn unexpand_line(self: &mut Self, line: String) {
-        let mut convert = true;
-        let mut spaces: usize = 0;
         let mut column: usize = 0;
+        let mut run_start: usize = 0;
+        let mut run: Vec<u8> = Vec::new();
+        let mut leading = true;
 
         for c in line.bytes() {
             match c {
-                b' ' => {
-                    spaces += 1;
-                }
+                b' ' | b'\t' => {
+                    if run.is_empty() {
+                        run_start = column;
+                    }
+                    run.push(c);
+
+                    column = if c == b'\t' {
+                        self.stops.next_stop(column).unwrap_or(column + 1)
+                    } else {
+                        column + 1
+                    };
+                },
                 b'\x08' => {
-                    spaces -= !!spaces;
-                    column -= !!column;
-                }
+                    self.flush_run(run_start, column, &run, false);
+                    run.clear();
+                    column = column.saturating_sub(1);
+                },
                 _ => {
-                    if spaces > 2 && convert {
-                        self.output.write("\t".repeat(spaces / 2).as_bytes()).expect("write error");
-                        spaces = spaces % 2;
-                    }
+                    self.flush_run(run_start, column, &run, self.convert(leading));
+                    run.clear();
+                    leading = false;
 
-                    self.output
-                        .write(String::from(" ").repeat(spaces).as_bytes())
-                        .expect("write error");
-                    spaces = 0;
+                    self.output.write(&[c]).expect("write error");
+                    column += 1;
+                },
+            };
+        }
 
+        self.flush_run(run_start, column, &run, self.convert(leading));
 
-                    self.output.write(&[c as u8]).expect("write error");
-                }
-            };
+        self.output.write(b"\n").expect("write error");
+        self.output.flush().expect("write error");
+    }
 
+    /// Whether a blank run starting while `leading` (still in the line's leading blanks)
+    /// is eligible for tab conversion: always when leading, and also when not leading if
+    /// `-a` is set and `--first-only` hasn't overridden it back to leading-only.
+    fn convert(&self, leading: bool) -> bool { leading || (self.all && !self.first_only) }
+
+    /// Emits the blank run spanning output columns `[start, end)`. If `eligible`, walks
+    /// the configured tab stops to replace it with the fewest tabs/spaces that reach the
+    /// same column; a stop is only worth a tab if reaching it consumes at least two
+    /// blanks. Otherwise the run is passed through unchanged.
+    fn flush_run(&mut self, start: usize, end: usize, run: &[u8], eligible: bool) {
+        if run.is_empty() {
+            return;
+        }
 
-            column += 1;
-            let blank = c == b' ' || c == b'\t';
-            convert &= self.all || blank;
+        if !eligible {
+            self.output.write(run).expect("write error");
+            return;
         }
 
-        self.output.write(b"\n").expect("write error");
-        self.output.flush();
+        let mut pos = start;
+
+        while let Some(stop) = self.stops.next_stop(pos).filter(|&stop| stop <= end) {
+            if stop - pos >= 2 {
+                self.output.write(b"\t").expect("write error");
+            } else {
+                self.output.write(" ".repeat(stop - pos).as_bytes()).expect("write error");
+            }
+
+            pos = stop;
+        }
+
+        if pos < end {
+            self.output.write(" ".repeat(end - pos).as_bytes()).expect("write error");
+        }
     }
 }
 
@@ -108,6 +193,9 @@ fn main() {
             }
         } else {
             let fd = File::open(file_path).unwrap();
+            // A single forward pass over the whole file: tell the kernel to read
+            // ahead aggressively instead of relying on its default heuristic.
+            advise_sequential(fd.as_raw_fd());
             let reader = BufReader::new(fd);
             for line in reader.lines() {
                 unexpand.unexpand_line(line.unwrap());
@@ -0,0 +1,61 @@
+use super::*;
+
+fn unexpand(all: bool, first_only: bool, tabs: &[&str], line: &str) -> String {
+    let tabs: Vec<String> = tabs.iter().map(|s| s.to_string()).collect();
+    let mut unexpand = Unexpand::with_output(all, first_only, &tabs, Vec::new());
+
+    unexpand.unexpand_line(line.to_string());
+
+    String::from_utf8(unexpand.output).unwrap()
+}
+
+#[test]
+fn single_tab_size_converts_leading_runs() {
+    // 8 leading spaces land exactly on a stop, so they collapse to a single tab.
+    assert_eq!(unexpand(false, false, &["8"], "        foo"), "\tfoo\n");
+}
+
+#[test]
+fn single_tab_size_leaves_a_lone_space_alone() {
+    // A single blank doesn't save anything over a tab, so it's left as a space.
+    assert_eq!(unexpand(false, false, &["8"], " foo"), " foo\n");
+}
+
+#[test]
+fn explicit_list_keeps_trailing_literal_spaces_short_of_next_stop() {
+    // Stops at columns 4 and 8; the run only reaches column 6, so it's one tab to
+    // column 4 plus two literal spaces for the remainder past the last stop it reaches.
+    assert_eq!(unexpand(false, false, &["4", "8"], "      foo"), "\t  foo\n");
+}
+
+#[test]
+fn default_only_converts_leading_blanks() {
+    // Without -a, a run of blanks in the middle of the line is left untouched even
+    // though it's wide enough to be worth a tab.
+    let spaced = "        foo        bar";
+    assert_eq!(unexpand(false, false, &["8"], spaced), "\tfoo        bar\n");
+}
+
+#[test]
+fn all_flag_also_converts_mid_line_runs() {
+    // With -a the mid-line run is eligible too. It starts at column 11 (not a stop),
+    // so it can only reach the next stop at 16 before running out of room, leaving
+    // the rest as literal spaces.
+    let spaced = "        foo        bar";
+    assert_eq!(unexpand(true, false, &["8"], spaced), "\tfoo\t   bar\n");
+}
+
+#[test]
+fn first_only_overrides_all_back_to_leading_only() {
+    // --first-only cancels -a's effect on non-leading runs, back to default behavior.
+    let spaced = "        foo        bar";
+    assert_eq!(unexpand(true, true, &["8"], spaced), "\tfoo        bar\n");
+}
+
+#[test]
+fn backspace_mid_run_flushes_and_moves_column_back() {
+    // The backspace flushes the run accumulated so far verbatim (the flush call in
+    // the backspace arm is hardcoded ineligible) and rewinds the column from 3 to 2, so
+    // the space right after it fills column 2 again and the line stays at column 3.
+    assert_eq!(unexpand(false, false, &["8"], "   \x08 foo"), "    foo\n");
+}